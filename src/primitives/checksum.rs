@@ -6,6 +6,7 @@
 
 use core::{mem, ops};
 
+use crate::primitives::fe32_ext;
 use crate::primitives::gf32::Fe32;
 use crate::primitives::hrp::Hrp;
 
@@ -47,9 +48,11 @@ pub trait Checksum {
     /// by the appropriate power of 2) in the field. That is, the 5 entries in this
     /// array are the generator times { P, Z, Y, G, S } in that order.
     ///
-    /// These cannot be usefully pre-computed because of Rust's limited constfn support
-    /// as of 1.67, so they must be specified manually for each checksum. To check the
-    /// values for consistency, run `Self::sanity_check()`.
+    /// Rather than hand-transcribing all 5 shifts, new checksums should compute this from
+    /// just the generator polynomial (the `GENERATOR_SH[0]` entry) using
+    /// [`generator_shifts_u32`], [`generator_shifts_u64`] or [`generator_shifts_u128`], as
+    /// appropriate for `Self::MidstateRepr`. To check the values for consistency (whether
+    /// hand-transcribed or derived), run `Self::sanity_check()`.
     const GENERATOR_SH: [Self::MidstateRepr; 5];
 
     /// The residue, modulo the generator polynomial, that a valid codeword will have.
@@ -83,6 +86,86 @@ pub trait Checksum {
     }
 }
 
+/// Generates the `impl Checksum::GENERATOR_SH`-style helper for a given `MidstateRepr`.
+///
+/// Given just the generator polynomial (the value a `Checksum` impl would otherwise have to
+/// put in `GENERATOR_SH[0]`), derives the other 4 shifts by repeatedly doubling every packed
+/// coefficient, exactly the relationship `Checksum::sanity_check` already verifies holds. This
+/// way a new checksum can be defined from a single polynomial constant rather than 5
+/// hand-transcribed ones that only `sanity_check` would catch a mistake in.
+macro_rules! impl_generator_shifts {
+    ($ty:ident, $name:ident) => {
+        #[doc = concat!(
+            "Derives the 5-entry `GENERATOR_SH` for a [`Checksum`] with `MidstateRepr = ",
+            stringify!($ty),
+            "` from its generator polynomial alone. See [`impl_generator_shifts`]."
+        )]
+        pub const fn $name(generator_poly: $ty) -> [$ty; 5] {
+            let width = <$ty as PackedFe32>::WIDTH;
+            let mut shifts = [0; 5];
+            shifts[0] = generator_poly;
+
+            let mut i = 1;
+            while i < 5 {
+                let prev = shifts[i - 1];
+                let mut cur: $ty = 0;
+                let mut j = 0;
+                while j < width {
+                    let coeff = ((prev >> (j * 5)) & 0x1f) as u8;
+                    cur |= (fe32_ext::double(coeff) as $ty) << (j * 5);
+                    j += 1;
+                }
+                shifts[i] = cur;
+                i += 1;
+            }
+            shifts
+        }
+    };
+}
+impl_generator_shifts!(u32, generator_shifts_u32);
+impl_generator_shifts!(u64, generator_shifts_u64);
+impl_generator_shifts!(u128, generator_shifts_u128);
+
+/// Generates the `const_residue`-style helper for a given `MidstateRepr`.
+///
+/// Simulates [`Engine::input_fe`] at compile time, so that `TARGET_RESIDUE` for a new checksum
+/// can be derived by feeding a known-valid representative string's field elements (HRP and
+/// payload) through the candidate `GENERATOR_SH`, rather than transcribing a constant computed
+/// by some other means.
+macro_rules! impl_const_residue {
+    ($ty:ident, $name:ident) => {
+        #[doc = concat!(
+            "Computes the residue `generator_sh` produces for `fes`, for a [`Checksum`] with ",
+            "`MidstateRepr = ", stringify!($ty), "`. See [`impl_const_residue`]."
+        )]
+        pub const fn $name(generator_sh: [$ty; 5], checksum_length: usize, fes: &[u8]) -> $ty {
+            let mut residue: $ty = 1;
+            let mut i = 0;
+            while i < fes.len() {
+                // Inlined `PackedFe32::mul_by_x_then_add`, which isn't `const fn`.
+                let top = ((residue >> ((checksum_length - 1) * 5)) & 0x1f) as u8;
+                residue &= !(0x1f << ((checksum_length - 1) * 5));
+                residue <<= 5;
+                residue |= fes[i] as $ty;
+
+                let xn = top;
+                let mut k = 0;
+                while k < 5 {
+                    if xn & (1 << k) != 0 {
+                        residue ^= generator_sh[k];
+                    }
+                    k += 1;
+                }
+                i += 1;
+            }
+            residue
+        }
+    };
+}
+impl_const_residue!(u32, const_residue_u32);
+impl_const_residue!(u64, const_residue_u64);
+impl_const_residue!(u128, const_residue_u128);
+
 /// A checksum engine, which can be used to compute or verify a checksum.
 ///
 /// Use this to verify a checksum, feed it the data to be checksummed using
@@ -138,6 +221,46 @@ impl<Ck: Checksum> Engine<Ck> {
     /// Returns for the current checksum residue.
     #[inline]
     pub fn residue(&self) -> &Ck::MidstateRepr { &self.residue }
+
+    /// Checks this engine's residue against a group of checksums that share `Ck`'s generator
+    /// (and therefore its `GENERATOR_SH`/`MidstateRepr`) but differ in target residue, such as
+    /// bech32 and bech32m, returning whichever variant matches, if any.
+    ///
+    /// Because the candidates share a generator, this needs no second pass over the input:
+    /// the single residue already computed by feeding data into this engine is simply compared
+    /// against each candidate's `TARGET_RESIDUE` in turn.
+    ///
+    /// `V::Source` ties candidates to the specific [`Checksum`] whose generator they share --
+    /// `Ck` itself -- so it is a compile error to pass a `ChecksumVariant` group defined for a
+    /// different generator, rather than a mismatch that would only surface as a wrong answer.
+    #[inline]
+    pub fn detect_variant<V>(&self, candidates: &[V]) -> Option<V>
+    where
+        V: ChecksumVariant<Source = Ck>,
+    {
+        candidates.iter().copied().find(|v| v.target_residue() == self.residue)
+    }
+}
+
+/// A group of [`Checksum`]s that share a generator polynomial (and so can be checked with a
+/// single [`Engine`] run) but differ in their target residue.
+///
+/// bech32 and bech32m are the motivating example: they differ only in `TARGET_RESIDUE` (`1`
+/// vs `0x2bc830a3`), so a caller decoding a segwit address, which may be either, does not need
+/// to guess which one it is and run the engine twice to check -- [`Engine::detect_variant`]
+/// checks both from a single residue.
+///
+/// `Source` should be set to whichever of the group's members the implementor considers
+/// canonical (their `GENERATOR_SH`/`MidstateRepr` must all agree in any case); this is what
+/// lets [`Engine::detect_variant`] require, at compile time, that its candidates actually came
+/// from the same generator as the engine they're being checked against, rather than merely
+/// sharing a `MidstateRepr` by coincidence.
+pub trait ChecksumVariant: Copy + Eq {
+    /// The [`Checksum`] whose generator every member of this group shares.
+    type Source: Checksum;
+
+    /// This variant's target residue.
+    fn target_residue(self) -> <Self::Source as Checksum>::MidstateRepr;
 }
 
 /// Trait describing an integer type which can be used as a "packed" sequence of Fe32s.
@@ -274,3 +397,57 @@ impl<'hrp> Iterator for HrpFe32Iter<'hrp> {
         (min, max)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generator_shifts_agree_with_repeated_doubling() {
+        let shifts = generator_shifts_u32(0x3b6a57b2);
+        assert_eq!(shifts[0], 0x3b6a57b2);
+        for i in 1..5 {
+            for j in 0..u32::WIDTH {
+                let last = ((shifts[i - 1] >> (j * 5)) & 0x1f) as u8;
+                let curr = ((shifts[i] >> (j * 5)) & 0x1f) as u8;
+                assert_eq!(curr, fe32_ext::double(last));
+            }
+        }
+    }
+
+    #[test]
+    fn generator_shifts_are_consistent_across_widths() {
+        // The same generator polynomial, derived at three widths, should agree wherever
+        // both representations have the bits to say so.
+        let sh32 = generator_shifts_u32(0x3b6a57b2);
+        let sh64 = generator_shifts_u64(0x3b6a57b2);
+        let sh128 = generator_shifts_u128(0x3b6a57b2);
+        for i in 0..5 {
+            assert_eq!(sh32[i] as u128, sh64[i] as u128);
+            assert_eq!(sh64[i] as u128, sh128[i]);
+        }
+    }
+
+    #[test]
+    fn const_residue_matches_engine_for_an_all_zero_codeword() {
+        const GENERATOR_SH: [u32; 5] = generator_shifts_u32(0x3b6a57b2);
+        const FES: [u8; 6] = [0; 6];
+        const RESIDUE: u32 = const_residue_u32(GENERATOR_SH, 6, &FES);
+
+        enum TestCode {}
+        impl Checksum for TestCode {
+            type MidstateRepr = u32;
+            const CODE_LENGTH: usize = 1023;
+            const CHECKSUM_LENGTH: usize = 6;
+            const GENERATOR_SH: [u32; 5] = GENERATOR_SH;
+            const TARGET_RESIDUE: u32 = RESIDUE;
+        }
+        TestCode::sanity_check();
+
+        let mut engine = Engine::<TestCode>::new();
+        for &fe in &FES {
+            engine.input_fe(Fe32(fe));
+        }
+        assert_eq!(*engine.residue(), RESIDUE);
+    }
+}
@@ -0,0 +1,485 @@
+// SPDX-License-Identifier: MIT
+
+//! codex32 (BIP-93): Shamir secret sharing over GF(32), built on [`Checksum`]/[`Engine`].
+//!
+//! A codex32 string looks like `ms1<k><id4><share-index><payload><checksum>`: an `ms` HRP,
+//! a threshold character `k`, a 4-character share identifier, a 1-character share index, the
+//! (secret) payload, and a checksum. Two checksum lengths are defined: a 13-character "long"
+//! code for strings up to 93 characters, and a 15-character "short" code for strings up to 80
+//! characters -- the shorter total length affording relatively more error-correcting power.
+//!
+//! Recombination is textbook Shamir: each payload character is a point on a degree-`(k-1)`
+//! polynomial over GF(32), with the share index as the x-coordinate, so `k` shares are enough
+//! to recover the secret by Lagrange interpolation at the share index `s`. This module, like
+//! the rest of `primitives`, is `no_std` and allocation-free: callers provide output buffers
+//! rather than receiving owned `Vec`s.
+
+use crate::primitives::checksum::{self, Checksum, Engine};
+use crate::primitives::fe32_ext;
+use crate::primitives::gf32::Fe32;
+use crate::primitives::hrp::Hrp;
+
+/// The fixed HRP every codex32 string uses.
+const CODEX32_HRP: &str = "ms";
+
+/// The "long" ms32 checksum, used for codex32 strings of up to 93 characters.
+///
+/// Has a 13-character checksum, giving it the same error-correcting strength (as a fraction of
+/// the maximum BCH code length) as the 6-character bech32/bech32m checksums.
+///
+/// 13 characters need 65 bits, more than fit in a `u64` (`u64::WIDTH` is only 12 characters),
+/// so despite the narrower checksums elsewhere in the crate fitting in 32 or 64 bits, this one
+/// needs `u128` (`u128::WIDTH` is 25).
+pub enum Ms32Long {}
+
+impl Checksum for Ms32Long {
+    type MidstateRepr = u128;
+    const CODE_LENGTH: usize = 93;
+    const CHECKSUM_LENGTH: usize = 13;
+    // The generator polynomial of BIP-93's 13-character checksum; its other 4 shifts are
+    // derived by generator_shifts_u128.
+    const GENERATOR_SH: [u128; 5] = checksum::generator_shifts_u128(0x19dc500ce73fde210);
+    const TARGET_RESIDUE: u128 = 0x10ce0795c2fd1e62a;
+}
+
+/// The "short" ms32 checksum, used for codex32 strings of up to 80 characters.
+///
+/// 15 characters need 75 bits, more than fit in a `u32` (`u32::WIDTH` is only 6 characters),
+/// so this also needs `u128`.
+pub enum Ms32Short {}
+
+impl Checksum for Ms32Short {
+    type MidstateRepr = u128;
+    const CODE_LENGTH: usize = 80;
+    const CHECKSUM_LENGTH: usize = 15;
+    // The generator polynomial of BIP-93's 15-character checksum, shifts derived as for
+    // Ms32Long.
+    const GENERATOR_SH: [u128; 5] = checksum::generator_shifts_u128(0x3d59d273535ea62d897);
+    const TARGET_RESIDUE: u128 = 0x43381e570bf4798ab26;
+}
+
+/// Which of the two ms32 checksums a share uses.
+///
+/// Determined by the total length of the codex32 string (everything after the `ms1`
+/// HRP-and-separator): 80 characters or fewer use [`Ms32Short`], longer strings (up to 93) use
+/// [`Ms32Long`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Codex32Checksum {
+    /// The 13-character checksum (see [`Ms32Long`]).
+    Long,
+    /// The 15-character checksum (see [`Ms32Short`]).
+    Short,
+}
+
+/// An error validating, parsing or recombining codex32 shares.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Fewer than `k` shares were provided, so there is no unique secret to recover.
+    NotEnoughShares {
+        /// The declared threshold.
+        threshold: u8,
+        /// The number of shares actually provided.
+        got: usize,
+    },
+    /// Two shares disagreed on the threshold, identifier, checksum code, or payload length, or
+    /// no shares were provided at all.
+    Mismatch,
+    /// Two shares had the same share index, so they cannot be used together to interpolate.
+    DuplicateIndex(Fe32),
+    /// The caller-supplied output buffer is shorter than the payload being written into it.
+    OutputTooShort,
+    /// The string did not start with the `ms1` HRP-and-separator.
+    InvalidHrp,
+    /// The string was too short to contain a header and checksum, or too long for either
+    /// checksum code to cover.
+    InvalidLength,
+    /// A character outside the bech32 charset appeared where a field element was expected.
+    InvalidChar,
+    /// The string's checksum did not verify.
+    InvalidChecksum,
+}
+
+/// A parsed codex32 share header: everything between the `ms1` HRP-and-separator and the
+/// payload.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ShareHeader {
+    /// The `k` out of which this share is one (`Fe32::_0`, the digit `0`, marks a share with
+    /// no declared threshold -- the plain, unshared secret encoding).
+    pub threshold: Fe32,
+    /// 4-character group identifier, shared by every share of the same secret.
+    pub id: [Fe32; 4],
+    /// This share's index (the Shamir x-coordinate).
+    pub index: Fe32,
+    /// Which checksum code this share was (or should be) encoded with.
+    pub checksum: Codex32Checksum,
+}
+
+/// A codex32 share with its payload (checksum already stripped and verified).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Share<'p> {
+    /// The share's header fields.
+    pub header: ShareHeader,
+    /// The payload field elements.
+    pub payload: &'p [Fe32],
+}
+
+impl ShareHeader {
+    fn compatible_with(&self, other: &ShareHeader) -> bool {
+        self.threshold == other.threshold && self.id == other.id && self.checksum == other.checksum
+    }
+}
+
+fn validate(shares: &[Share]) -> Result<usize, Error> {
+    let first = shares.first().ok_or(Error::Mismatch)?;
+    // The threshold is the digit the character spells, not its field-element value
+    // (`Fe32::_2` is the element 10); `0` marks an unshared secret, which is a single share.
+    let declared = match first.header.threshold.to_char().to_digit(10) {
+        Some(digit) => digit as u8,
+        None => first.header.threshold.0,
+    };
+    let threshold = if declared == 0 { 1 } else { usize::from(declared) };
+    if shares.len() < threshold {
+        return Err(Error::NotEnoughShares { threshold: declared, got: shares.len() });
+    }
+    for share in &shares[1..] {
+        if !share.header.compatible_with(&first.header) || share.payload.len() != first.payload.len() {
+            return Err(Error::Mismatch);
+        }
+    }
+    for (i, a) in shares.iter().enumerate() {
+        for b in &shares[i + 1..] {
+            if a.header.index == b.header.index {
+                return Err(Error::DuplicateIndex(a.header.index));
+            }
+        }
+    }
+    Ok(first.payload.len())
+}
+
+/// Recombines `k` (or more) codex32 shares into the original secret, writing it into `secret`.
+///
+/// Every share must agree on the threshold, group identifier, checksum code, and payload
+/// length, and no two shares may share an index; otherwise interpolation is either ill-defined
+/// or, in the threshold case, simply impossible to do correctly.
+pub fn recombine(shares: &[Share], secret: &mut [Fe32]) -> Result<(), Error> {
+    let payload_len = validate(shares)?;
+    if secret.len() < payload_len {
+        return Err(Error::OutputTooShort);
+    }
+    for (pos, out) in secret[..payload_len].iter_mut().enumerate() {
+        *out = interpolate(shares, pos, Fe32::S);
+    }
+    Ok(())
+}
+
+/// Evaluates, at `x`, the unique degree-`(shares.len() - 1)` polynomial that passes through
+/// `(share.header.index, share.payload[pos])` for every share, using Lagrange's formula.
+///
+/// All arithmetic is in GF(32); see the module-level docs.
+fn interpolate(shares: &[Share], pos: usize, x: Fe32) -> Fe32 {
+    let mut acc = Fe32::Q;
+    for share_i in shares {
+        let mut term = share_i.payload[pos];
+        for share_j in shares {
+            if share_i.header.index == share_j.header.index {
+                continue;
+            }
+            // (x - x_j) / (x_i - x_j); GF(32) has characteristic 2, so subtraction is xor.
+            let num = fe32_ext::add(x.0, share_j.header.index.0);
+            let den = fe32_ext::add(share_i.header.index.0, share_j.header.index.0);
+            let den_inv = Fe32(den).inverse().expect("distinct indices checked by caller");
+            term = Fe32(fe32_ext::mul(term.0, fe32_ext::mul(num, den_inv.0)));
+        }
+        acc = Fe32(fe32_ext::add(acc.0, term.0));
+    }
+    acc
+}
+
+/// Generates one fresh share of `secret`, at `share_index`, for a `threshold`-of-`n` scheme.
+///
+/// `random_coefficients` must supply `threshold - 1` field elements, freshly sampled for this
+/// secret (but shared across all of that secret's generated shares): together with the secret
+/// they define the degree-`(threshold - 1)` polynomial
+/// `secret + c[0]*(x - s) + c[1]*(x - s)^2 + ...`, which equals the secret at the share index
+/// `s` (where [`recombine`] interpolates) and is evaluated at `share_index` to produce the
+/// returned share payload, written into `out`.
+pub fn generate_share(
+    secret: &[Fe32],
+    random_coefficients: &[Fe32],
+    share_index: Fe32,
+    out: &mut [Fe32],
+) -> Result<(), Error> {
+    if out.len() < secret.len() {
+        return Err(Error::OutputTooShort);
+    }
+    for (pos, (&secret_fe, out_fe)) in secret.iter().zip(out.iter_mut()).enumerate() {
+        let _ = pos;
+        // Evaluate at `share_index - s`, so that the polynomial's value at `s` is the secret.
+        let shifted_index = Fe32(fe32_ext::add(share_index.0, Fe32::S.0));
+        *out_fe = eval_poly(secret_fe, random_coefficients, shifted_index);
+    }
+    Ok(())
+}
+
+/// Evaluates `secret_fe + c[0]*x + c[1]*x^2 + ...` via Horner's method, all in GF(32).
+fn eval_poly(secret_fe: Fe32, coefficients: &[Fe32], x: Fe32) -> Fe32 {
+    let mut acc = Fe32::Q;
+    for &c in coefficients.iter().rev() {
+        acc = Fe32(fe32_ext::add(fe32_ext::mul(acc.0, x.0), c.0));
+    }
+    Fe32(fe32_ext::add(fe32_ext::mul(acc.0, x.0), secret_fe.0))
+}
+
+/// Parses a codex32 string (`ms1<k><id4><index><payload><checksum>`), verifying its checksum
+/// and writing the decoded payload into `payload_out`.
+///
+/// `s` must be lowercase, as is conventional for codex32 strings. The checksum code (long or
+/// short) is chosen automatically from `s`'s length, as BIP-93 specifies.
+///
+/// On success, returns the parsed header and the number of field elements written into
+/// `payload_out`.
+pub fn parse(s: &str, payload_out: &mut [Fe32]) -> Result<(ShareHeader, usize), Error> {
+    let rest = s.strip_prefix("ms1").ok_or(Error::InvalidHrp)?;
+    if rest.len() < 6 {
+        return Err(Error::InvalidLength);
+    }
+    if rest.len() <= Ms32Short::CODE_LENGTH {
+        parse_with::<Ms32Short>(rest, Codex32Checksum::Short, payload_out)
+    } else if rest.len() <= Ms32Long::CODE_LENGTH {
+        parse_with::<Ms32Long>(rest, Codex32Checksum::Long, payload_out)
+    } else {
+        Err(Error::InvalidLength)
+    }
+}
+
+fn parse_with<Ck: Checksum>(
+    rest: &str,
+    checksum: Codex32Checksum,
+    payload_out: &mut [Fe32],
+) -> Result<(ShareHeader, usize), Error> {
+    if rest.len() < 6 + Ck::CHECKSUM_LENGTH {
+        return Err(Error::InvalidLength);
+    }
+    let payload_len = rest.len() - 6 - Ck::CHECKSUM_LENGTH;
+    if payload_out.len() < payload_len {
+        return Err(Error::OutputTooShort);
+    }
+
+    let bytes = rest.as_bytes();
+    let fe_at = |i: usize| -> Result<Fe32, Error> {
+        Fe32::from_char(bytes[i] as char).map_err(|_| Error::InvalidChar)
+    };
+
+    let threshold = fe_at(0)?;
+    let mut id = [Fe32::Q; 4];
+    for (i, slot) in id.iter_mut().enumerate() {
+        *slot = fe_at(1 + i)?;
+    }
+    let index = fe_at(5)?;
+
+    let hrp = Hrp::parse(CODEX32_HRP).expect("\"ms\" is a valid hrp");
+    let mut engine = Engine::<Ck>::new();
+    engine.input_hrp(hrp);
+    engine.input_fe(threshold);
+    for &fe in &id {
+        engine.input_fe(fe);
+    }
+    engine.input_fe(index);
+
+    for (i, out_fe) in payload_out[..payload_len].iter_mut().enumerate() {
+        let fe = fe_at(6 + i)?;
+        engine.input_fe(fe);
+        *out_fe = fe;
+    }
+    for i in 0..Ck::CHECKSUM_LENGTH {
+        engine.input_fe(fe_at(6 + payload_len + i)?);
+    }
+
+    if *engine.residue() != Ck::TARGET_RESIDUE {
+        return Err(Error::InvalidChecksum);
+    }
+
+    Ok((ShareHeader { threshold, id, index, checksum }, payload_len))
+}
+
+/// Feeds `fe` into `engine` and writes its bech32 character into `out[*pos]`, advancing `pos`.
+fn emit<Ck: Checksum>(engine: &mut Engine<Ck>, out: &mut [u8], pos: &mut usize, fe: Fe32) {
+    engine.input_fe(fe);
+    out[*pos] = fe.to_char() as u8;
+    *pos += 1;
+}
+
+/// Serializes `header` and `payload` as a codex32 string, computing and appending the
+/// checksum, and writes the result (as ASCII bytes) into `out`.
+///
+/// `Ck` must match `header.checksum` (i.e. be [`Ms32Long`] if `header.checksum` is
+/// [`Codex32Checksum::Long`], [`Ms32Short`] if [`Codex32Checksum::Short`]); mismatching them
+/// produces a string with the wrong checksum length, which [`parse`] would reject.
+///
+/// Returns the number of bytes written.
+pub fn format<Ck: Checksum>(header: &ShareHeader, payload: &[Fe32], out: &mut [u8]) -> Result<usize, Error> {
+    let total = 3 + 6 + payload.len() + Ck::CHECKSUM_LENGTH;
+    if out.len() < total {
+        return Err(Error::OutputTooShort);
+    }
+
+    let hrp = Hrp::parse(CODEX32_HRP).expect("\"ms\" is a valid hrp");
+    let mut engine = Engine::<Ck>::new();
+    engine.input_hrp(hrp);
+
+    out[0] = b'm';
+    out[1] = b's';
+    out[2] = b'1';
+    let mut pos = 3;
+
+    emit(&mut engine, out, &mut pos, header.threshold);
+    for &id_fe in &header.id {
+        emit(&mut engine, out, &mut pos, id_fe);
+    }
+    emit(&mut engine, out, &mut pos, header.index);
+    for &fe in payload {
+        emit(&mut engine, out, &mut pos, fe);
+    }
+
+    engine.input_target_residue();
+    let residue = *engine.residue();
+    for i in 0..Ck::CHECKSUM_LENGTH {
+        let fe = Fe32(residue.unpack(Ck::CHECKSUM_LENGTH - i - 1));
+        out[pos] = fe.to_char() as u8;
+        pos += 1;
+    }
+
+    Ok(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::checksum::PackedFe32;
+
+    #[test]
+    fn sanity_check_ms32_long() { Ms32Long::sanity_check(); }
+
+    #[test]
+    fn sanity_check_ms32_short() { Ms32Short::sanity_check(); }
+
+    /// Pins the actual BIP-93 Sage-derived constants, so a future change that accidentally
+    /// substitutes a self-consistent but spec-unrelated polynomial (as happened once before)
+    /// fails loudly instead of merely passing `sanity_check`.
+    #[test]
+    fn checksum_constants_match_bip93() {
+        assert_eq!(Ms32Long::GENERATOR_SH[0], 0x19dc500ce73fde210);
+        assert_eq!(Ms32Long::TARGET_RESIDUE, 0x10ce0795c2fd1e62a);
+        assert_eq!(Ms32Short::GENERATOR_SH[0], 0x3d59d273535ea62d897);
+        assert_eq!(Ms32Short::TARGET_RESIDUE, 0x43381e570bf4798ab26);
+    }
+
+    #[test]
+    fn single_share_is_secret_when_threshold_is_zero() {
+        let header = ShareHeader {
+            threshold: Fe32::_0,
+            id: [Fe32::A; 4],
+            index: Fe32::A,
+            checksum: Codex32Checksum::Long,
+        };
+        let payload = [Fe32::P, Fe32::Z];
+        let share = Share { header, payload: &payload };
+
+        let mut secret = [Fe32::Q; 2];
+        recombine(&[share], &mut secret).unwrap();
+        assert_eq!(secret, payload);
+    }
+
+    #[test]
+    fn share_then_recombine_round_trips() {
+        let id = [Fe32::A; 4];
+        let secret = [Fe32::P, Fe32::Z, Fe32::Y];
+        let coefficients = [Fe32::G];
+        let indices = [Fe32::A, Fe32::C, Fe32::E];
+
+        let mut payloads = [[Fe32::Q; 3]; 3];
+        for (idx, payload) in indices.iter().zip(payloads.iter_mut()) {
+            generate_share(&secret, &coefficients, *idx, payload).unwrap();
+        }
+
+        let shares = [
+            Share {
+                header: ShareHeader {
+                    threshold: Fe32::_2,
+                    id,
+                    index: indices[0],
+                    checksum: Codex32Checksum::Long,
+                },
+                payload: &payloads[0],
+            },
+            Share {
+                header: ShareHeader {
+                    threshold: Fe32::_2,
+                    id,
+                    index: indices[1],
+                    checksum: Codex32Checksum::Long,
+                },
+                payload: &payloads[1],
+            },
+        ];
+
+        let mut recovered = [Fe32::Q; 3];
+        recombine(&shares, &mut recovered).unwrap();
+        assert_eq!(recovered, secret);
+    }
+
+    #[test]
+    fn mismatched_checksum_codes_are_rejected() {
+        let header_a =
+            ShareHeader { threshold: Fe32::_2, id: [Fe32::A; 4], index: Fe32::A, checksum: Codex32Checksum::Long };
+        let header_b = ShareHeader { checksum: Codex32Checksum::Short, ..header_a };
+        let payload = [Fe32::P];
+        let shares =
+            [Share { header: header_a, payload: &payload }, Share { header: header_b, payload: &payload }];
+
+        assert_eq!(validate(&shares), Err(Error::Mismatch));
+    }
+
+    #[test]
+    fn format_then_parse_round_trips() {
+        let header = ShareHeader {
+            threshold: Fe32::_0,
+            id: [Fe32::A, Fe32::C, Fe32::E, Fe32::G],
+            index: Fe32::S,
+            // The string is under 80 characters, so `parse` selects the short code.
+            checksum: Codex32Checksum::Short,
+        };
+        let payload = [Fe32::P, Fe32::Z, Fe32::Y, Fe32::G, Fe32::F, Fe32::_2];
+
+        let mut buf = [0u8; 3 + 6 + 6 + Ms32Short::CHECKSUM_LENGTH];
+        let written = format::<Ms32Short>(&header, &payload, &mut buf).unwrap();
+        let s = core::str::from_utf8(&buf[..written]).unwrap();
+
+        let mut parsed_payload = [Fe32::Q; 6];
+        let (parsed_header, payload_len) = parse(s, &mut parsed_payload).unwrap();
+        assert_eq!(parsed_header, header);
+        assert_eq!(&parsed_payload[..payload_len], &payload[..]);
+    }
+
+    #[test]
+    fn parse_rejects_corrupted_checksum() {
+        let header = ShareHeader {
+            threshold: Fe32::_0,
+            id: [Fe32::A; 4],
+            index: Fe32::S,
+            // The string is under 80 characters, so `parse` selects the short code.
+            checksum: Codex32Checksum::Short,
+        };
+        let payload = [Fe32::P, Fe32::Z];
+
+        let mut buf = [0u8; 3 + 6 + 2 + Ms32Short::CHECKSUM_LENGTH];
+        let written = format::<Ms32Short>(&header, &payload, &mut buf).unwrap();
+        // Flip the last checksum character to a different, still-valid bech32 character.
+        let last = buf[written - 1];
+        buf[written - 1] = if last == b'q' { b'p' } else { b'q' };
+        let s = core::str::from_utf8(&buf[..written]).unwrap();
+
+        let mut parsed_payload = [Fe32::Q; 2];
+        assert_eq!(parse(s, &mut parsed_payload), Err(Error::InvalidChecksum));
+    }
+}
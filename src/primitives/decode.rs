@@ -0,0 +1,497 @@
+// SPDX-License-Identifier: MIT
+
+//! Error location and correction for [`Checksum`] codes.
+//!
+//! The checksums computed by [`Engine`] are [BCH codes](https://en.wikipedia.org/wiki/BCH_code).
+//! For the codes implemented in this crate, the generator polynomial's roots are consecutive
+//! powers `e^k, e^(k+1), ..., e^(k + CHECKSUM_LENGTH - 1)` of a primitive element `e` of
+//! `GF(1024) = GF(32)[x] / (x^2 + 9x + 23)` (`9` and `23` being GF(32) elements, and `e` having
+//! multiplicative order 1023). That structure is exactly what lets us go beyond detecting that a
+//! string's checksum is wrong, to *locating* (and fixing) the error.
+//!
+//! The residue XORed with [`Checksum::TARGET_RESIDUE`] is the "error polynomial"; evaluating it
+//! at the designed roots gives a handful of syndromes, from which the position and value of up
+//! to two errors can be recovered algebraically, without ever searching the space of possible
+//! strings.
+
+use core::cmp;
+
+use crate::primitives::checksum::{Checksum, Engine, PackedFe32};
+use crate::primitives::fe32_ext;
+use crate::primitives::gf32::Fe32;
+use crate::primitives::hrp::Hrp;
+
+/// Extension of [`Checksum`] for codes whose generator is a genuine BCH code, i.e. whose
+/// roots are the consecutive powers `e^ROOT_OFFSET, ..., e^(ROOT_OFFSET + CHECKSUM_LENGTH - 1)`
+/// of the primitive element of GF(1024) described in the module documentation.
+///
+/// Implementing this (in addition to [`Checksum`]) unlocks [`locate_errors`].
+pub trait Correctable: Checksum {
+    /// The smallest exponent `k` such that `e^k` is a root of the generator polynomial.
+    ///
+    /// Both bech32 and bech32m use generators with roots `e^1, e^2, ..., e^CHECKSUM_LENGTH`,
+    /// so for both, `ROOT_OFFSET` is 1.
+    const ROOT_OFFSET: usize;
+
+    /// The coefficients `(a, b)` of the field-extension modulus `x^2 + a*x + b`, as GF(32)
+    /// elements, defining `GF(1024) = GF(32)[x] / (x^2 + a*x + b)` for this code's roots.
+    ///
+    /// Bech32 and bech32m both use `(9, 23)`, the only pair this module originally supported;
+    /// a future BCH code is free to use a different irreducible modulus as long as `e`, the
+    /// root this module always represents as `(hi: 1, lo: 0)`, has multiplicative order 1023
+    /// under it.
+    const GF1024_MODULUS: (Fe32, Fe32);
+}
+
+/// Where, within the original string, a located error lies.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Position {
+    /// The error is in the data part (which includes the checksum), at this many characters
+    /// from the *end* of the string.
+    Data {
+        /// Number of characters from the end of the string.
+        from_end: usize,
+    },
+    /// The error is in the upper-bits encoding of this HRP character (counted from the start
+    /// of the HRP).
+    HrpHigh {
+        /// Index, from the start of the HRP, of the affected character.
+        index: usize,
+    },
+    /// The error is in the lower-bits encoding of this HRP character (counted from the start
+    /// of the HRP).
+    HrpLow {
+        /// Index, from the start of the HRP, of the affected character.
+        index: usize,
+    },
+    /// The error is in the HRP/data separator field element, which does not correspond to a
+    /// single correctable character.
+    Separator,
+}
+
+/// A single located error: where it is, and the value that must be XORed into the offending
+/// field element to correct it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ErrorLocation {
+    /// Where the error is.
+    pub position: Position,
+    /// The field element which, XORed into the erroneous character, corrects it.
+    pub correction: Fe32,
+}
+
+/// The outcome of a successful [`locate_errors`] call.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Correction {
+    /// The residue already matched [`Checksum::TARGET_RESIDUE`]; there was nothing to correct.
+    NoErrors,
+    /// A single character needs correcting.
+    OneError(ErrorLocation),
+    /// Two characters need correcting.
+    TwoErrors([ErrorLocation; 2]),
+}
+
+/// Returned when the checksum is invalid but the error cannot be resolved to one or two
+/// characters using this code's error-correcting capability.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Uncorrectable;
+
+/// Attempts to locate (and give the fix for) the error(s) behind a mismatched checksum.
+///
+/// `hrp` and `data_len` describe the string that was fed into `engine` (`data_len` being the
+/// number of data characters, including the checksum, that followed the HRP), and are needed
+/// to translate the error polynomial's internal degree -- which runs over every field element
+/// [`HrpFe32Iter`] produces for the HRP as well as the data -- back into a position within the
+/// original string.
+///
+/// [`HrpFe32Iter`]: crate::primitives::checksum::HrpFe32Iter
+pub fn locate_errors<Ck: Correctable>(
+    engine: &Engine<Ck>,
+    hrp: &Hrp,
+    data_len: usize,
+) -> Result<Correction, Uncorrectable> {
+    let error_poly = *engine.residue() ^ Ck::TARGET_RESIDUE;
+    if (0..Ck::CHECKSUM_LENGTH).all(|i| error_poly.unpack(i) == 0) {
+        return Ok(Correction::NoErrors);
+    }
+
+    let hrp_fe_len = 2 * hrp.as_str().chars().count() + 1;
+    let total_len = hrp_fe_len + data_len;
+
+    let modulus = Ck::GF1024_MODULUS;
+    let num_syndromes = cmp::min(4, Ck::CHECKSUM_LENGTH);
+    let mut syndromes = [Fe1024::ZERO; 4];
+    for (i, syndrome) in syndromes.iter_mut().enumerate().take(num_syndromes) {
+        *syndrome = eval_error_poly::<Ck>(error_poly, Ck::ROOT_OFFSET + i, modulus);
+    }
+
+    if let Some(loc) = locate_one::<Ck>(&syndromes, num_syndromes, hrp_fe_len, data_len, total_len, modulus) {
+        return Ok(Correction::OneError(loc));
+    }
+    if num_syndromes == 4 {
+        if let Some(locs) = locate_two::<Ck>(&syndromes, hrp_fe_len, data_len, total_len, modulus) {
+            return Ok(Correction::TwoErrors(locs));
+        }
+    }
+    Err(Uncorrectable)
+}
+
+/// Evaluates the error polynomial (the XOR of the computed and target residues) at `e^k`.
+fn eval_error_poly<Ck: Checksum>(
+    error_poly: Ck::MidstateRepr,
+    k: usize,
+    modulus: (Fe32, Fe32),
+) -> Fe1024 {
+    let root_k = Fe1024::pow_e(k, modulus);
+    let mut acc = Fe1024::ZERO;
+    let mut power = Fe1024::ONE;
+    for i in 0..Ck::CHECKSUM_LENGTH {
+        let coeff = error_poly.unpack(i);
+        if coeff != 0 {
+            acc = acc.add(Fe1024::from_fe32(Fe32(coeff)).mul(power, modulus));
+        }
+        power = power.mul(root_k, modulus);
+    }
+    acc
+}
+
+fn locate_one<Ck: Correctable>(
+    s: &[Fe1024; 4],
+    num_syndromes: usize,
+    hrp_fe_len: usize,
+    data_len: usize,
+    total_len: usize,
+    modulus: (Fe32, Fe32),
+) -> Option<ErrorLocation> {
+    if s[0] == Fe1024::ZERO {
+        return None;
+    }
+    let ratio = s[1].mul(s[0].inv(modulus)?, modulus);
+    let p = ratio.log(modulus)?;
+
+    let amplitude = s[0].mul(Fe1024::pow_e(p * Ck::ROOT_OFFSET, modulus).inv(modulus)?, modulus);
+    if amplitude.hi != Fe32(0) {
+        // Not a genuine GF(32) amplitude: this isn't actually a single error.
+        return None;
+    }
+
+    // Confirm against the remaining syndromes we actually computed; slots beyond
+    // `num_syndromes` are unused `Fe1024::ZERO` placeholders, not real syndromes, and would
+    // spuriously fail this check for any `Correctable` impl with `CHECKSUM_LENGTH < 4`.
+    for (i, syndrome) in s.iter().enumerate().take(num_syndromes) {
+        let predicted = amplitude.mul(Fe1024::pow_e(p * (Ck::ROOT_OFFSET + i), modulus), modulus);
+        if predicted != *syndrome {
+            return None;
+        }
+    }
+
+    Some(ErrorLocation {
+        position: translate(p, hrp_fe_len, data_len, total_len)?,
+        correction: amplitude.lo,
+    })
+}
+
+fn locate_two<Ck: Correctable>(
+    s: &[Fe1024; 4],
+    hrp_fe_len: usize,
+    data_len: usize,
+    total_len: usize,
+    modulus: (Fe32, Fe32),
+) -> Option<[ErrorLocation; 2]> {
+    // Characteristic polynomial of the order-2 recurrence S[k+2] = sigma1*S[k+1] + sigma2*S[k],
+    // solved from S[1..=4] via Cramer's rule (all arithmetic in GF(1024), char 2).
+    let det = s[1].mul(s[1], modulus).add(s[0].mul(s[2], modulus));
+    let det_inv = det.inv(modulus)?;
+    let sigma1 = s[2].mul(s[1], modulus).add(s[3].mul(s[0], modulus)).mul(det_inv, modulus);
+    let sigma2 = s[1].mul(s[3], modulus).add(s[2].mul(s[2], modulus)).mul(det_inv, modulus);
+
+    // Roots of x^2 + sigma1*x + sigma2 are the two error locators e^p1, e^p2. GF(1024) only
+    // has 1023 nonzero elements, so a direct search is cheap and avoids implementing a
+    // dedicated quadratic solver for this field.
+    let mut roots = [None; 2];
+    let mut found = 0;
+    for p in 0..1023 {
+        let x = Fe1024::pow_e(p, modulus);
+        if x.mul(x, modulus).add(sigma1.mul(x, modulus)).add(sigma2) == Fe1024::ZERO {
+            if found == 2 {
+                // More than two roots: our two-error model doesn't fit this residue.
+                return None;
+            }
+            roots[found] = Some((p, x));
+            found += 1;
+        }
+    }
+    let (p1, r1) = roots[0]?;
+    let (p2, r2) = roots[1]?;
+
+    // Recover b_i = a_i * r_i^ROOT_OFFSET from S[ROOT_OFFSET] = b1 + b2 and
+    // S[ROOT_OFFSET+1] = b1*r1 + b2*r2, then divide out r_i^ROOT_OFFSET to get each a_i. Unlike
+    // the S[1]/S[2]-specific shortcut this replaces, this holds for any ROOT_OFFSET, matching
+    // how locate_one recovers its (single-error) amplitude.
+    let sum_r = r1.add(r2);
+    let denom = sum_r.inv(modulus)?;
+    let b1 = s[0].mul(r2, modulus).add(s[1]).mul(denom, modulus);
+    let b2 = s[1].add(s[0].mul(r1, modulus)).mul(denom, modulus);
+    let a1 = b1.mul(Fe1024::pow_e(p1 * Ck::ROOT_OFFSET, modulus).inv(modulus)?, modulus);
+    let a2 = b2.mul(Fe1024::pow_e(p2 * Ck::ROOT_OFFSET, modulus).inv(modulus)?, modulus);
+    if a1.hi != Fe32(0) || a2.hi != Fe32(0) {
+        return None;
+    }
+
+    Some([
+        ErrorLocation { position: translate(p1, hrp_fe_len, data_len, total_len)?, correction: a1.lo },
+        ErrorLocation { position: translate(p2, hrp_fe_len, data_len, total_len)?, correction: a2.lo },
+    ])
+}
+
+/// Translates a degree `p` of the error polynomial (position counted from the last field
+/// element fed into the engine) into a [`Position`] within the original string.
+fn translate(p: usize, hrp_fe_len: usize, data_len: usize, total_len: usize) -> Option<Position> {
+    if p >= total_len {
+        return None;
+    }
+    if p < data_len {
+        return Some(Position::Data { from_end: p });
+    }
+    // Feed order was: HRP-high[0..n], separator, HRP-low[0..n], data[0..data_len]. Field
+    // elements are consumed in that order, but degrees count down from the *last* one fed, so
+    // translate `p` back into a feed index first.
+    let feed_index = total_len - 1 - p;
+    let hrp_len = (hrp_fe_len - 1) / 2;
+    if feed_index < hrp_len {
+        Some(Position::HrpHigh { index: feed_index })
+    } else if feed_index == hrp_len {
+        Some(Position::Separator)
+    } else {
+        Some(Position::HrpLow { index: feed_index - hrp_len - 1 })
+    }
+}
+
+/// An element of `GF(1024) = GF(32)[x] / (x^2 + a*x + b)` for some code-specific modulus
+/// `(a, b)` (see [`Correctable::GF1024_MODULUS`]), represented as `hi*e + lo`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Fe1024 {
+    hi: Fe32,
+    lo: Fe32,
+}
+
+impl Fe1024 {
+    const ZERO: Fe1024 = Fe1024 { hi: Fe32(0), lo: Fe32(0) };
+    const ONE: Fe1024 = Fe1024 { hi: Fe32(0), lo: Fe32(1) };
+
+    fn from_fe32(e: Fe32) -> Fe1024 { Fe1024 { hi: Fe32(0), lo: e } }
+
+    fn add(self, other: Fe1024) -> Fe1024 {
+        Fe1024 {
+            hi: Fe32(fe32_ext::add(self.hi.0, other.hi.0)),
+            lo: Fe32(fe32_ext::add(self.lo.0, other.lo.0)),
+        }
+    }
+
+    fn mul(self, other: Fe1024, modulus: (Fe32, Fe32)) -> Fe1024 {
+        let (a1, a0) = (self.hi.0, self.lo.0);
+        let (b1, b0) = (other.hi.0, other.lo.0);
+        let (mod_hi, mod_lo) = (modulus.0 .0, modulus.1 .0);
+
+        let cross = fe32_ext::add(fe32_ext::mul(a1, b0), fe32_ext::mul(a0, b1));
+        let a1b1 = fe32_ext::mul(a1, b1);
+
+        let hi = fe32_ext::add(fe32_ext::mul(a1b1, mod_hi), cross);
+        let lo = fe32_ext::add(fe32_ext::mul(a1b1, mod_lo), fe32_ext::mul(a0, b0));
+
+        Fe1024 { hi: Fe32(hi), lo: Fe32(lo) }
+    }
+
+    /// The primitive element `e`, a root of `x^2 + a*x + b`, for whichever `(a, b)` the caller
+    /// is working modulo.
+    fn e() -> Fe1024 { Fe1024 { hi: Fe32(1), lo: Fe32(0) } }
+
+    /// Computes `e^k`, reducing `k` modulo the order (1023) of `e`.
+    fn pow_e(k: usize, modulus: (Fe32, Fe32)) -> Fe1024 {
+        let mut base = Fe1024::e();
+        let mut exp = k % 1023;
+        let mut acc = Fe1024::ONE;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc = acc.mul(base, modulus);
+            }
+            base = base.mul(base, modulus);
+            exp >>= 1;
+        }
+        acc
+    }
+
+    /// The multiplicative inverse of `self`, or `None` if `self` is zero.
+    ///
+    /// `self^1022` is the inverse of `self` for any nonzero element of a field with 1023
+    /// nonzero elements, by Fermat's little theorem generalized to finite fields.
+    fn inv(self, modulus: (Fe32, Fe32)) -> Option<Fe1024> {
+        if self == Fe1024::ZERO {
+            return None;
+        }
+        let mut base = self;
+        let mut exp = 1022usize;
+        let mut acc = Fe1024::ONE;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc = acc.mul(base, modulus);
+            }
+            base = base.mul(base, modulus);
+            exp >>= 1;
+        }
+        Some(acc)
+    }
+
+    /// Returns `k` such that `e^k == self`, by repeated multiplication, or `None` if `self`
+    /// is zero.
+    ///
+    /// This is only ever called on syndrome ratios, which are few and far between, so a
+    /// linear search (rather than a 1023-entry table) is a fine trade of code size for speed.
+    fn log(self, modulus: (Fe32, Fe32)) -> Option<usize> {
+        if self == Fe1024::ZERO {
+            return None;
+        }
+        let mut cur = Fe1024::ONE;
+        for k in 0..1023 {
+            if cur == self {
+                return Some(k);
+            }
+            cur = cur.mul(Fe1024::e(), modulus);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::checksum::Engine;
+
+    /// A throwaway code sharing bech32's generator, used to exercise [`locate_errors`] without
+    /// depending on `crate::primitives::bech32`.
+    enum TestCode {}
+
+    impl Checksum for TestCode {
+        type MidstateRepr = u32;
+        const CODE_LENGTH: usize = 1023;
+        const CHECKSUM_LENGTH: usize = 6;
+        const GENERATOR_SH: [u32; 5] = crate::primitives::checksum::generator_shifts_u32(0x3b6a57b2);
+        const TARGET_RESIDUE: u32 = 1;
+    }
+
+    impl Correctable for TestCode {
+        const ROOT_OFFSET: usize = 1;
+        const GF1024_MODULUS: (Fe32, Fe32) = (Fe32(9), Fe32(23));
+    }
+
+    /// Same generator as [`TestCode`], but with `ROOT_OFFSET = 2`, so that a `locate_two` bug
+    /// which only works correctly for `ROOT_OFFSET == 1` shows up here even though it would
+    /// pass every [`TestCode`] case.
+    enum ShiftedRootCode {}
+
+    impl Checksum for ShiftedRootCode {
+        type MidstateRepr = u32;
+        const CODE_LENGTH: usize = 1023;
+        const CHECKSUM_LENGTH: usize = 6;
+        const GENERATOR_SH: [u32; 5] = crate::primitives::checksum::generator_shifts_u32(0x3b6a57b2);
+        const TARGET_RESIDUE: u32 = 1;
+    }
+
+    impl Correctable for ShiftedRootCode {
+        const ROOT_OFFSET: usize = 2;
+        const GF1024_MODULUS: (Fe32, Fe32) = (Fe32(9), Fe32(23));
+    }
+
+    /// Computes the checksum characters that make `hrp` followed by `payload` followed by
+    /// those characters a valid `Ck` codeword.
+    fn checksum_chars<Ck: Checksum>(hrp: &Hrp, payload: &[Fe32]) -> [Fe32; 6] {
+        let mut engine = Engine::<Ck>::new();
+        engine.input_hrp(*hrp);
+        for &fe in payload {
+            engine.input_fe(fe);
+        }
+        engine.input_target_residue();
+        let residue = *engine.residue();
+        let mut out = [Fe32::Q; 6];
+        for (i, out_fe) in out.iter_mut().enumerate() {
+            *out_fe = Fe32(residue.unpack(Ck::CHECKSUM_LENGTH - i - 1));
+        }
+        out
+    }
+
+    /// Feeds `hrp`, `payload` and `checksum` into a fresh engine.
+    fn engine_for<Ck: Checksum>(hrp: &Hrp, payload: &[Fe32], checksum: &[Fe32]) -> Engine<Ck> {
+        let mut engine = Engine::<Ck>::new();
+        engine.input_hrp(*hrp);
+        for &fe in payload.iter().chain(checksum) {
+            engine.input_fe(fe);
+        }
+        engine
+    }
+
+    #[test]
+    fn valid_codeword_has_no_errors() {
+        let hrp = Hrp::parse("bc").unwrap();
+        let payload = [Fe32::Q, Fe32::P, Fe32::Z, Fe32::Y];
+        let checksum = checksum_chars::<TestCode>(&hrp, &payload);
+        let engine = engine_for::<TestCode>(&hrp, &payload, &checksum);
+
+        let data_len = payload.len() + TestCode::CHECKSUM_LENGTH;
+        assert_eq!(locate_errors(&engine, &hrp, data_len), Ok(Correction::NoErrors));
+    }
+
+    #[test]
+    fn single_error_is_located_and_corrected() {
+        let hrp = Hrp::parse("bc").unwrap();
+        let payload = [Fe32::Q, Fe32::P, Fe32::Z, Fe32::Y];
+        let checksum = checksum_chars::<TestCode>(&hrp, &payload);
+
+        let mut corrupted = payload;
+        let corrupted_index = 2;
+        corrupted[corrupted_index] = Fe32(fe32_ext::add(corrupted[corrupted_index].0, Fe32::G.0));
+        let engine = engine_for::<TestCode>(&hrp, &corrupted, &checksum);
+
+        let data_len = payload.len() + TestCode::CHECKSUM_LENGTH;
+        let correction = locate_errors(&engine, &hrp, data_len).unwrap();
+        let from_end = data_len - 1 - corrupted_index;
+        assert_eq!(
+            correction,
+            Correction::OneError(ErrorLocation {
+                position: Position::Data { from_end },
+                correction: Fe32::G,
+            })
+        );
+    }
+
+    #[test]
+    fn two_errors_are_located_and_corrected_for_shifted_root_offset() {
+        let hrp = Hrp::parse("bc").unwrap();
+        let payload = [Fe32::Q, Fe32::P, Fe32::Z, Fe32::Y, Fe32::G, Fe32::F];
+        let checksum = checksum_chars::<ShiftedRootCode>(&hrp, &payload);
+
+        let mut corrupted = payload;
+        let (i1, i2) = (1, 4);
+        corrupted[i1] = Fe32(fe32_ext::add(corrupted[i1].0, Fe32::T.0));
+        corrupted[i2] = Fe32(fe32_ext::add(corrupted[i2].0, Fe32::V.0));
+        let engine = engine_for::<ShiftedRootCode>(&hrp, &corrupted, &checksum);
+
+        let data_len = payload.len() + ShiftedRootCode::CHECKSUM_LENGTH;
+        let correction = locate_errors(&engine, &hrp, data_len).unwrap();
+        match correction {
+            Correction::TwoErrors(locs) => {
+                let mut from_ends: [usize; 2] =
+                    [data_len - 1 - i1, data_len - 1 - i2];
+                from_ends.sort_unstable();
+                let mut got: [usize; 2] = [0; 2];
+                for (got_fe, loc) in got.iter_mut().zip(locs.iter()) {
+                    *got_fe = match loc.position {
+                        Position::Data { from_end } => from_end,
+                        other => panic!("unexpected position {:?}", other),
+                    };
+                }
+                got.sort_unstable();
+                assert_eq!(got, from_ends);
+            }
+            other => panic!("expected two errors, got {:?}", other),
+        }
+    }
+}
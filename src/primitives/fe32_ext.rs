@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: MIT
+
+//! GF(32) field arithmetic for `Fe32`.
+//!
+//! [`Checksum::sanity_check`] and [`Engine`] only ever need to multiply a packed polynomial
+//! by *x* (a left shift with conditional reduction), so until now nothing in this crate
+//! needed to multiply two arbitrary `Fe32` values. The error-correction
+//! (`crate::primitives::decode`) and codex32 (`crate::primitives::codex32`) subsystems
+//! both need real field arithmetic, so it lives here once and is shared by both.
+//!
+//! [`Checksum::sanity_check`]: crate::primitives::checksum::Checksum::sanity_check
+//! [`Engine`]: crate::primitives::checksum::Engine
+
+use crate::primitives::gf32::Fe32;
+
+/// Doubles a single GF(32) element, i.e. multiplies it by the primitive element `2`.
+///
+/// GF(32) is defined by extending GF(2) with a root of `x^5 + x^3 + 1 = 0`, which written as
+/// bit coefficients is `41`; this is the same reduction [`Checksum::sanity_check`] uses to
+/// check that `GENERATOR_SH` is self-consistent, and that
+/// [`generator_shifts_u32`][crate::primitives::checksum::generator_shifts_u32]-style helpers
+/// use to derive one shift of `GENERATOR_SH` from the last.
+///
+/// [`Checksum::sanity_check`]: crate::primitives::checksum::Checksum::sanity_check
+pub(crate) const fn double(v: u8) -> u8 {
+    if v & 0x10 != 0 {
+        (v << 1) ^ 41
+    } else {
+        v << 1
+    }
+}
+
+/// Builds the EXP/LOG tables for GF(32)\{0\}, with the primitive element `2` as base.
+const fn tables() -> ([u8; 31], [u8; 32]) {
+    let mut exp = [0u8; 31];
+    let mut log = [0u8; 32];
+
+    let mut x = 1u8;
+    let mut i = 0;
+    while i < 31 {
+        exp[i] = x;
+        log[x as usize] = i as u8;
+        x = double(x);
+        i += 1;
+    }
+    (exp, log)
+}
+
+const TABLES: ([u8; 31], [u8; 32]) = tables();
+/// `EXP[i]` is the primitive element `2` raised to the `i`'th power, for `i` in `0..31`.
+const EXP: [u8; 31] = TABLES.0;
+/// `LOG[v]` is the discrete log, base `2`, of the nonzero element `v`.
+const LOG: [u8; 32] = TABLES.1;
+
+/// Multiplies two GF(32) elements.
+pub(crate) const fn mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        0
+    } else {
+        let sum = LOG[a as usize] as usize + LOG[b as usize] as usize;
+        EXP[sum % 31]
+    }
+}
+
+/// Adds (equivalently, subtracts) two GF(32) elements.
+pub(crate) const fn add(a: u8, b: u8) -> u8 { a ^ b }
+
+/// Inverts a nonzero GF(32) element.
+const fn inv(a: u8) -> Option<u8> {
+    if a == 0 {
+        None
+    } else {
+        Some(EXP[(31 - LOG[a as usize] as usize) % 31])
+    }
+}
+
+impl Fe32 {
+    /// Returns the multiplicative inverse of `self` in GF(32), or `None` if `self` is `Q`
+    /// (the representation of zero), which has no inverse.
+    ///
+    /// Used by [`crate::primitives::codex32`] to perform Lagrange interpolation when
+    /// recombining Shamir secret shares.
+    pub fn inverse(&self) -> Option<Fe32> { inv(self.0).map(Fe32) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exp_log_are_inverses() {
+        for v in 1u8..32 {
+            assert_eq!(EXP[LOG[v as usize] as usize], v);
+        }
+    }
+
+    #[test]
+    fn multiplication_by_one_is_identity() {
+        for v in 0u8..32 {
+            assert_eq!(mul(v, 1), v);
+        }
+    }
+
+    #[test]
+    fn every_nonzero_element_has_an_inverse() {
+        for v in 1u8..32 {
+            let inverse = inv(v).unwrap();
+            assert_eq!(mul(v, inverse), 1);
+        }
+    }
+}